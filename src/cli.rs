@@ -0,0 +1,51 @@
+//! Command-line surface for the scraper: `login`, `list`, `stats`, and `download`, each sharing
+//! the same session-loading logic so a valid stored session skips the interactive login form.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "leanpub-scraper", about = "Scrape your Leanpub author dashboard")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Output format for commands that print structured data (`list`, `stats`).
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    pub format: Format,
+
+    /// If headless reCAPTCHA polling times out, relaunch headful and wait for it to be solved
+    /// by hand instead of giving up.
+    #[arg(long, global = true)]
+    pub interactive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Verify (or establish) a login session and persist it for future runs.
+    Login,
+    /// Print the catalog of published books.
+    List,
+    /// Print sales/readership stats for each published book.
+    Stats,
+    /// Download published book artifacts (PDF/EPUB/MOBI).
+    Download {
+        /// Directory to download into; files land at `<out_dir>/<slug>/<slug>.<ext>`.
+        #[arg(long, default_value = "downloads")]
+        out_dir: PathBuf,
+
+        /// Gitignore-style glob a file must match to be downloaded (repeatable).
+        #[arg(long = "include", default_value = "*")]
+        include: Vec<String>,
+
+        /// Gitignore-style glob that excludes a matching file even if included (repeatable).
+        #[arg(long = "exclude")]
+        exclude: Vec<String>,
+    },
+}
@@ -0,0 +1,179 @@
+//! Downloads generated book artifacts (PDF/EPUB/MOBI) for already-published books, reusing the
+//! authenticated Playwright context's cookies so the plain HTTP downloads don't need their own
+//! login flow.
+
+use crate::BookLink;
+use glob::Pattern;
+use playwright::api::{BrowserContext, Page};
+use std::path::PathBuf;
+
+/// The three artifact formats Leanpub generates per book.
+const FORMATS: &[&str] = &["pdf", "epub", "mobi"];
+
+/// Controls where files land and which formats are downloaded.
+pub struct DownloadOptions {
+    pub out_dir: PathBuf,
+    /// Gitignore-style glob patterns; a file must match at least one to be downloaded.
+    pub include: Vec<String>,
+    /// Gitignore-style glob patterns; a file matching any of these is skipped.
+    pub exclude: Vec<String>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            out_dir: PathBuf::from("downloads"),
+            include: vec!["*".to_string()],
+            exclude: Vec::new(),
+        }
+    }
+}
+
+/// Outcome of attempting to download a single book artifact.
+pub struct DownloadResult {
+    pub slug: String,
+    pub file_name: String,
+    pub outcome: Result<PathBuf, String>,
+}
+
+fn matches_filters(file_name: &str, opts: &DownloadOptions) -> bool {
+    let included = opts.include.iter().any(|pat| {
+        Pattern::new(pat)
+            .map(|p| p.matches(file_name))
+            .unwrap_or(false)
+    });
+    if !included {
+        return false;
+    }
+    !opts.exclude.iter().any(|pat| {
+        Pattern::new(pat)
+            .map(|p| p.matches(file_name))
+            .unwrap_or(false)
+    })
+}
+
+/// Build a `reqwest::Client` carrying the same cookies as the Playwright context, so downloads
+/// hit Leanpub as the logged-in user.
+async fn client_with_session_cookies(
+    context: &BrowserContext,
+) -> Result<reqwest::Client, playwright::Error> {
+    let cookies = context.cookies(&[]).await?;
+    let jar = reqwest::cookie::Jar::default();
+    let leanpub_url: reqwest::Url = "https://leanpub.com".parse().expect("static URL");
+    for cookie in cookies {
+        jar.add_cookie_str(&format!("{}={}", cookie.name, cookie.value), &leanpub_url);
+    }
+    Ok(reqwest::Client::builder()
+        .cookie_provider(std::sync::Arc::new(jar))
+        .build()
+        .expect("reqwest client builder"))
+}
+
+/// Download every published book's artifacts into `<out_dir>/<slug>/<slug>.<ext>`, skipping
+/// formats filtered out by `opts.include`/`opts.exclude` and files that already exist with a
+/// matching size. Reuses `page`'s authenticated context for the HTTP downloads.
+pub async fn download_books(
+    page: &Page,
+    books: &[BookLink],
+    opts: &DownloadOptions,
+) -> Result<Vec<DownloadResult>, playwright::Error> {
+    let client = client_with_session_cookies(&page.context()).await?;
+    let mut results = Vec::new();
+
+    for book in books {
+        let book_dir = opts.out_dir.join(&book.slug);
+        for format in FORMATS {
+            let file_name = format!("{}.{}", book.slug, format);
+            if !matches_filters(&file_name, opts) {
+                continue;
+            }
+
+            let url = format!("https://leanpub.com/{}/download/{}", book.slug, format);
+            let dest = book_dir.join(&file_name);
+            let outcome = download_one(&client, &url, &dest).await;
+            results.push(DownloadResult {
+                slug: book.slug.clone(),
+                file_name,
+                outcome,
+            });
+        }
+    }
+
+    Ok(results)
+}
+
+/// Download a single file to `dest`, skipping it if it already exists with a matching size.
+/// Checks the existing file's size against the response's `Content-Length` header before
+/// pulling the body, so an already-downloaded multi-MB artifact isn't re-read over the wire
+/// just to discover it didn't need re-downloading.
+async fn download_one(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &std::path::Path,
+) -> Result<PathBuf, String> {
+    // A HEAD failure (unsupported method, transient error, missing Content-Length) just falls
+    // through to the normal GET below rather than failing the whole download.
+    if let Ok(existing) = tokio::fs::metadata(dest).await {
+        if let Ok(head) = client.head(url).send().await {
+            if head.status().is_success() && head.content_length() == Some(existing.len()) {
+                return Ok(dest.to_path_buf());
+            }
+        }
+    }
+
+    let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {} for {}", response.status(), url));
+    }
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tokio::fs::write(dest, &bytes)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(dest.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(include: &[&str], exclude: &[&str]) -> DownloadOptions {
+        DownloadOptions {
+            out_dir: PathBuf::from("downloads"),
+            include: include.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn default_include_matches_everything() {
+        let o = DownloadOptions::default();
+        assert!(matches_filters("my-book.pdf", &o));
+        assert!(matches_filters("my-book.epub", &o));
+    }
+
+    #[test]
+    fn include_glob_restricts_to_matching_formats() {
+        let o = opts(&["*.pdf"], &[]);
+        assert!(matches_filters("my-book.pdf", &o));
+        assert!(!matches_filters("my-book.epub", &o));
+    }
+
+    #[test]
+    fn exclude_takes_precedence_over_include() {
+        let o = opts(&["*"], &["*.mobi"]);
+        assert!(matches_filters("my-book.pdf", &o));
+        assert!(!matches_filters("my-book.mobi", &o));
+    }
+
+    #[test]
+    fn no_include_pattern_matches_nothing() {
+        let o = opts(&[], &[]);
+        assert!(!matches_filters("my-book.pdf", &o));
+    }
+}
@@ -1,6 +1,17 @@
+mod cli;
+mod download;
+mod storage;
+
+use clap::Parser;
+use cli::{Cli, Command, Format};
 use playwright::Playwright;
-use playwright::api::Page;
-use serde::Deserialize;
+use playwright::api::{BrowserContext, Page, StorageState};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Default location for the persisted Playwright storage state (cookies + localStorage),
+/// used when `LEANPUB_SESSION_FILE` is not set.
+const DEFAULT_SESSION_FILE: &str = ".leanpub_session.json";
 
 #[derive(Debug, Deserialize)]
 struct FormField {
@@ -10,10 +21,10 @@ struct FormField {
     field_type: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct BookLink {
-    slug: String,
-    title: String,
+    pub(crate) slug: String,
+    pub(crate) title: String,
 }
 
 /// Verify that the login succeeded by navigating to the published books page
@@ -41,6 +52,54 @@ pub async fn verify_login(page: &Page) -> Result<bool, playwright::Error> {
     Ok(success)
 }
 
+/// Path to the persisted Playwright storage state file, overridable via `LEANPUB_SESSION_FILE`.
+fn session_file_path() -> PathBuf {
+    std::env::var("LEANPUB_SESSION_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_SESSION_FILE))
+}
+
+/// Thin wrapper around a Playwright storage-state file (cookies + localStorage) that lets
+/// `login()` skip the reCAPTCHA/credential flow whenever a valid session is already on disk.
+struct Session {
+    path: PathBuf,
+}
+
+impl Session {
+    fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    /// Build a fresh browser context, restoring cookies/localStorage from disk if present.
+    async fn build_context(
+        &self,
+        chromium: &playwright::api::BrowserType,
+    ) -> Result<BrowserContext, playwright::Error> {
+        let browser = chromium.launcher().headless(true).launch().await?;
+        let mut builder = browser.context_builder();
+        if self.exists() {
+            let raw = tokio::fs::read_to_string(&self.path).await?;
+            let state: StorageState = serde_json::from_str(&raw)?;
+            builder = builder.storage_state(state);
+        }
+        Ok(builder.build().await?)
+    }
+
+    /// Snapshot the context's current cookies/localStorage to disk for the next run.
+    async fn persist(&self, context: &BrowserContext) -> Result<(), playwright::Error> {
+        let state = context.storage_state().await?;
+        let json = serde_json::to_string_pretty(&state)
+            .expect("StorageState always serializes to JSON");
+        tokio::fs::write(&self.path, json).await?;
+        println!("Saved session state to {}", self.path.display());
+        Ok(())
+    }
+}
+
 /// After a successful login (and while on the published books page) collect slug/title pairs.
 pub async fn fetch_published_books(page: &Page) -> Result<Vec<BookLink>, playwright::Error> {
     // JavaScript executed in page to find links whose path ends with /overview (book overview pages)
@@ -66,41 +125,235 @@ pub async fn fetch_published_books(page: &Page) -> Result<Vec<BookLink>, playwri
     Ok(books)
 }
 
-/// Perform the entire login flow: load login page, wait for reCAPTCHA, submit credentials, verify dashboard.
-pub async fn login() -> Result<(), playwright::Error> {
-    let playwright = Playwright::initialize().await?;
-    playwright.prepare()?; // Install browsers
-    let chromium = playwright.chromium();
-    let browser = chromium.launcher().headless(true).launch().await?;
-    let context = browser.context_builder().build().await?;
+/// Numeric sales/readership metrics for a single published book, scraped from its
+/// author-dashboard overview page.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BookStats {
+    pub(crate) slug: String,
+    pub(crate) copies_sold: u64,
+    pub(crate) royalties_usd: f64,
+    pub(crate) readers: u64,
+    pub(crate) last_published_at: Option<String>,
+}
+
+/// Maximum number of book-stats pages fetched concurrently, to stay polite to leanpub.com.
+const STATS_CONCURRENCY: usize = 4;
+
+/// Navigate to a single book's dashboard/overview page and scrape its sales metrics.
+pub async fn fetch_book_stats(page: &Page, slug: &str) -> Result<BookStats, playwright::Error> {
+    let url = format!("https://leanpub.com/author_dashboard/books/{}", slug);
+    page.goto_builder(&url).goto().await?;
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await; // allow dynamic content to load
+
+    // JavaScript executed in page to pull the numeric stats off the overview dashboard
+    let js = r#"() => {
+        const num = (sel) => {
+            const el = document.querySelector(sel);
+            if (!el) return 0;
+            const digits = (el.textContent || '').replace(/[^0-9.]/g, '');
+            return digits ? Number(digits) : 0;
+        };
+        const lastPublished = document.querySelector('[data-test="last-published-at"]');
+        return {
+            copies_sold: num('[data-test="copies-sold"]'),
+            royalties_usd: num('[data-test="total-royalties"]'),
+            readers: num('[data-test="reader-count"]'),
+            last_published_at: lastPublished ? lastPublished.getAttribute('datetime') : null,
+        };
+    }"#;
+
+    #[derive(Debug, Deserialize)]
+    struct RawStats {
+        copies_sold: u64,
+        royalties_usd: f64,
+        readers: u64,
+        last_published_at: Option<String>,
+    }
+    let raw: RawStats = page.eval(js).await?;
+    Ok(BookStats {
+        slug: slug.to_string(),
+        copies_sold: raw.copies_sold,
+        royalties_usd: raw.royalties_usd,
+        readers: raw.readers,
+        last_published_at: raw.last_published_at,
+    })
+}
+
+/// Fetch the catalog and then stats for every published book; see [`fetch_stats_for_books`].
+pub async fn fetch_all_book_stats(page: &Page) -> Result<Vec<BookStats>, playwright::Error> {
+    let books = fetch_published_books(page).await?;
+    fetch_stats_for_books(page, &books).await
+}
+
+/// Fetch stats for a known set of books, each on its own page so lookups can run concurrently
+/// (bounded by [`STATS_CONCURRENCY`]), isolating per-book failures so one bad overview page
+/// doesn't abort the whole run.
+pub async fn fetch_stats_for_books(
+    page: &Page,
+    books: &[BookLink],
+) -> Result<Vec<BookStats>, playwright::Error> {
+    let context = page.context();
+    let mut stats = Vec::with_capacity(books.len());
+    for chunk in books.chunks(STATS_CONCURRENCY) {
+        let results = futures::future::join_all(chunk.iter().map(|b| async {
+            let book_page = context.new_page().await?;
+            let result = fetch_book_stats(&book_page, &b.slug).await;
+            if let Err(e) = book_page.close(None).await {
+                eprintln!("Failed to close page for '{}': {}", b.slug, e);
+            }
+            result
+        }))
+        .await;
+        for (book, result) in chunk.iter().zip(results) {
+            match result {
+                Ok(s) => stats.push(s),
+                Err(e) => eprintln!("Failed to fetch stats for '{}': {}", book.slug, e),
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Close an about-to-be-discarded context, logging rather than failing the caller if the
+/// underlying browser/driver is already gone (e.g. a human closed the window mid-login).
+async fn close_context(context: &BrowserContext) {
+    if let Err(e) = context.close().await {
+        eprintln!("Failed to close browser context: {}", e);
+    }
+}
+
+/// Establish an authenticated `(context, page)` pair: reuse a persisted session if it still
+/// verifies, otherwise fall back to the full reCAPTCHA + credential form flow and persist the
+/// resulting session for next time. Every subcommand goes through this first. Returns `None`
+/// (having already reported why) if neither the stored session nor the form flow succeed.
+async fn ensure_session(
+    chromium: &playwright::api::BrowserType,
+    interactive: bool,
+) -> Result<Option<(BrowserContext, Page)>, playwright::Error> {
+    let session = Session::new(session_file_path());
+
+    if session.exists() {
+        let context = session.build_context(chromium).await?;
+        let page = context.new_page().await?;
+        if verify_login(&page).await? {
+            println!("Restored session from {}", session.path.display());
+            return Ok(Some((context, page)));
+        }
+        eprintln!("Stored session is no longer valid; falling back to form login.");
+        close_context(&context).await;
+    }
+
+    let context = chromium
+        .launcher()
+        .headless(true)
+        .launch()
+        .await?
+        .context_builder()
+        .build()
+        .await?;
     let page = context.new_page().await?;
-    page.goto_builder("https://leanpub.com/login")
-        .goto()
+    if form_login(&page, false).await? {
+        session.persist(&context).await?;
+        return Ok(Some((context, page)));
+    }
+
+    if !interactive {
+        close_context(&context).await;
+        return Ok(None);
+    }
+
+    // Headless login failed (most likely the reCAPTCHA never got solved); relaunch headful so
+    // a human can clear the challenge, then retry the same form flow against that window.
+    println!("Relaunching headful for interactive reCAPTCHA solving...");
+    close_context(&context).await;
+    let context = chromium
+        .launcher()
+        .headless(false)
+        .launch()
+        .await?
+        .context_builder()
+        .build()
         .await?;
+    let page = context.new_page().await?;
+    if !form_login(&page, true).await? {
+        close_context(&context).await;
+        return Ok(None);
+    }
+    session.persist(&context).await?;
+    Ok(Some((context, page)))
+}
+
+/// Print a list of records as plain text (one per line via `to_line`) or as a JSON array.
+fn print_records<T: Serialize>(records: &[T], format: Format, to_line: impl Fn(&T) -> String) {
+    match format {
+        Format::Text => {
+            for record in records {
+                println!("{}", to_line(record));
+            }
+        }
+        Format::Json => match serde_json::to_string_pretty(records) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize output as JSON: {}", e),
+        },
+    }
+}
+
+/// Open `db_path` and save one snapshot row per book, isolated from the caller's error type
+/// since a storage failure shouldn't abort an otherwise-successful scrape.
+async fn persist_snapshot(
+    db_path: &str,
+    books: &[BookLink],
+    stats: &[BookStats],
+) -> Result<(), sqlx::Error> {
+    let pool = storage::open_db(db_path).await?;
+    storage::save_snapshot(&pool, books, stats).await?;
+    println!("Saved snapshot of {} book(s) to {}", stats.len(), db_path);
+    Ok(())
+}
+
+/// JS snippet checking whether the hidden g-recaptcha response field has been populated.
+const CAPTCHA_FILLED_JS: &str = r#"() => {
+    const el = document.querySelector("input[name^='g-recaptcha-response'], textarea[name='g-recaptcha-response'], input[name^='g-recaptcha-response-data']");
+    return el && el.value ? el.value : '';
+}"#;
 
-    // Wait for JS to populate the g-recaptcha hidden field (polling up to ~15s)
-    for attempt in 0..30 {
-        // 30 * 500ms = 15s max
-        let captcha_val: String = page
-            .eval(r#"() => {
-                const el = document.querySelector("input[name^='g-recaptcha-response'], textarea[name='g-recaptcha-response'], input[name^='g-recaptcha-response-data']");
-                return el && el.value ? el.value : '';
-            }"#)
-            .await
-            .unwrap_or_default();
+/// Poll for the reCAPTCHA field to be populated. In non-interactive mode this gives up after
+/// ~15s and returns `false` (leaving the caller to proceed anyway or suggest `--interactive`).
+/// In interactive mode it blocks indefinitely, since a human is expected to solve the challenge
+/// in the visible, headful browser window.
+async fn wait_for_recaptcha(page: &Page, interactive: bool) -> Result<bool, playwright::Error> {
+    let max_attempts = if interactive { u32::MAX } else { 30 };
+    if interactive {
+        println!("Waiting for reCAPTCHA to be solved by hand in the visible browser window...");
+    }
+    for attempt in 0..max_attempts {
+        let captcha_val: String = page.eval(CAPTCHA_FILLED_JS).await.unwrap_or_default();
         if !captcha_val.is_empty() {
             println!(
                 "reCAPTCHA field populated after {} attempt(s) (~{} ms)",
                 attempt + 1,
-                (attempt + 1) * 500
+                (attempt as u64 + 1) * 500
             );
-            break;
-        }
-        if attempt == 29 {
-            println!("reCAPTCHA field not populated within timeout; proceeding anyway.");
+            return Ok(true);
         }
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
+    Ok(false)
+}
+
+/// Run the reCAPTCHA-wait + credential-fill form flow and verify the result.
+/// Returns `true` once the dashboard has been reached and verified.
+async fn form_login(page: &Page, interactive: bool) -> Result<bool, playwright::Error> {
+    page.goto_builder("https://leanpub.com/login")
+        .goto()
+        .await?;
+
+    if !wait_for_recaptcha(page, interactive).await? {
+        println!(
+            "reCAPTCHA field not populated within timeout; proceeding anyway. \
+             Pass --interactive to solve it by hand in a visible browser instead."
+        );
+    }
 
     // Evaluate in the page context to extract all input fields within the first form
     let js = r#"() => {
@@ -137,34 +390,24 @@ pub async fn login() -> Result<(), playwright::Error> {
         eprintln!(
             "LEANPUB_EMAIL or LEANPUB_PASSWORD missing in environment; skipping form submission."
         );
-        return Ok(());
+        return Ok(false);
     }
 
-    // Escape single quotes for JS embedding
-    let safe_email = email.replace('\'', "\\'");
-    let safe_password = password.replace('\'', "\\'");
-    let fill_and_submit = format!(
-        r#"() => {{
-        const emailInput = document.querySelector("input[name='session[email]']");
-        if(emailInput) emailInput.value = '{email}';
-        const pwInput = document.querySelector("input[name='session[password]']");
-        if(pwInput) pwInput.value = '{password}';
-        const form = emailInput ? emailInput.form : document.querySelector('form');
-        if(form) {{
-            const btn = form.querySelector("input[type=submit],button[type=submit]");
-            if(btn) btn.click(); else form.submit();
-        }}
-        return !!(emailInput && pwInput);
-    }}"#,
-        email = safe_email,
-        password = safe_password
-    );
-
-    let filled: bool = page.eval(&fill_and_submit).await?;
-    if filled {
-        println!("Filled credentials and submitted form.");
-    } else {
-        eprintln!("Failed to locate form fields to fill.");
+    // Fill via typed locators rather than interpolating credentials into evaluated JS, so a
+    // password containing quotes/backslashes/newlines can never break out of the script.
+    page.fill_builder("input[name='session[email]']", &email)
+        .fill()
+        .await?;
+    page.fill_builder("input[name='session[password]']", &password)
+        .fill()
+        .await?;
+    match page
+        .click_builder("input[type='submit'], button[type='submit']")
+        .click()
+        .await
+    {
+        Ok(()) => println!("Filled credentials and submitted form."),
+        Err(e) => eprintln!("Failed to submit login form: {}", e),
     }
 
     // Poll for navigation / dashboard appearance
@@ -187,28 +430,79 @@ pub async fn login() -> Result<(), playwright::Error> {
         println!("User indicator snippet: {}", ind.trim());
     }
 
-    if !email.is_empty() {
-        match verify_login(&page).await? {
-            true => match fetch_published_books(&page).await {
-                Ok(list) => {
-                    println!("Published books ({}):", list.len());
-                    for b in list {
-                        println!("  {} => {}", b.slug, b.title);
+    let verified = verify_login(page).await?;
+    if !verified {
+        eprintln!("Login failed; exiting.");
+    }
+    Ok(verified)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), playwright::Error> {
+    let cli = Cli::parse();
+
+    let playwright = Playwright::initialize().await?;
+    playwright.prepare()?; // Install browsers
+    let chromium = playwright.chromium();
+
+    let Some((_context, page)) = ensure_session(&chromium, cli.interactive).await? else {
+        eprintln!("Login failed; exiting.");
+        return Ok(());
+    };
+
+    match cli.command {
+        Command::Login => {
+            println!("Logged in.");
+        }
+        Command::List => match fetch_published_books(&page).await {
+            Ok(books) => print_records(&books, cli.format, |b| format!("{} => {}", b.slug, b.title)),
+            Err(e) => eprintln!("Failed to fetch published books: {}", e),
+        },
+        Command::Stats => match fetch_published_books(&page).await {
+            Ok(books) => match fetch_stats_for_books(&page, &books).await {
+                Ok(stats) => {
+                    print_records(&stats, cli.format, |s| {
+                        format!(
+                            "{}: {} copies, ${:.2} royalties, {} readers",
+                            s.slug, s.copies_sold, s.royalties_usd, s.readers
+                        )
+                    });
+                    if let Ok(db_path) = std::env::var("LEANPUB_DB") {
+                        if let Err(e) = persist_snapshot(&db_path, &books, &stats).await {
+                            eprintln!("Failed to persist snapshot to '{}': {}", db_path, e);
+                        }
                     }
                 }
-                Err(e) => eprintln!("Failed to fetch published books: {}", e),
+                Err(e) => eprintln!("Failed to fetch book stats: {}", e),
             },
-            false => {
-                eprintln!("Login failed; exiting.");
-                return Ok(());
+            Err(e) => eprintln!("Failed to fetch published books: {}", e),
+        },
+        Command::Download {
+            out_dir,
+            include,
+            exclude,
+        } => {
+            let opts = download::DownloadOptions {
+                out_dir,
+                include,
+                exclude,
+            };
+            match fetch_published_books(&page).await {
+                Ok(books) => match download::download_books(&page, &books, &opts).await {
+                    Ok(results) => {
+                        for r in results {
+                            match r.outcome {
+                                Ok(path) => println!("{}/{}: saved to {}", r.slug, r.file_name, path.display()),
+                                Err(e) => eprintln!("{}/{}: {}", r.slug, r.file_name, e),
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("Download failed: {}", e),
+                },
+                Err(e) => eprintln!("Failed to fetch published books: {}", e),
             }
         }
     }
 
     Ok(())
 }
-
-#[tokio::main]
-async fn main() -> Result<(), playwright::Error> {
-    login().await
-}
@@ -0,0 +1,176 @@
+//! SQLite-backed persistence for scrape results, so repeated runs accumulate a history of
+//! `BookStats` snapshots that can later be charted instead of diffed out of console logs.
+
+use crate::{BookLink, BookStats};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+
+/// Open (creating if needed) the SQLite database at `path` and run schema migrations.
+pub async fn open_db(path: &str) -> Result<SqlitePool, sqlx::Error> {
+    let url = format!("sqlite://{}?mode=rwc", path);
+    let pool = SqlitePoolOptions::new().max_connections(5).connect(&url).await?;
+    migrate(&pool).await?;
+    Ok(pool)
+}
+
+/// Create the `books` and `book_snapshots` tables if they don't already exist.
+async fn migrate(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS books (
+            slug TEXT PRIMARY KEY,
+            title TEXT NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS book_snapshots (
+            slug TEXT NOT NULL,
+            scraped_at TEXT NOT NULL,
+            copies_sold INTEGER NOT NULL,
+            royalties REAL NOT NULL,
+            readers INTEGER NOT NULL,
+            PRIMARY KEY (slug, scraped_at)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Persist a scrape run: upsert each book's title into `books`, then append one row per book
+/// into `book_snapshots`, all timestamped with the same `scraped_at` so they form one run.
+/// `books` supplies the titles (from [`crate::fetch_published_books`]); entries in `stats`
+/// without a matching slug in `books` are skipped.
+pub async fn save_snapshot(
+    pool: &SqlitePool,
+    books: &[BookLink],
+    stats: &[BookStats],
+) -> Result<(), sqlx::Error> {
+    let scraped_at = chrono::Utc::now().to_rfc3339();
+    let mut tx = pool.begin().await?;
+    for s in stats {
+        let Some(book) = books.iter().find(|b| b.slug == s.slug) else {
+            eprintln!("No title found for slug '{}'; skipping snapshot row.", s.slug);
+            continue;
+        };
+
+        sqlx::query("INSERT INTO books (slug, title) VALUES (?, ?) ON CONFLICT(slug) DO UPDATE SET title = excluded.title")
+            .bind(&book.slug)
+            .bind(&book.title)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO book_snapshots (slug, scraped_at, copies_sold, royalties, readers) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&s.slug)
+        .bind(&scraped_at)
+        .bind(s.copies_sold as i64)
+        .bind(s.royalties_usd)
+        .bind(s.readers as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_db() -> (SqlitePool, tempfile::TempDir) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let db_path = dir.path().join("snapshots.sqlite");
+        let pool = open_db(db_path.to_str().expect("utf8 path"))
+            .await
+            .expect("open_db should create and migrate the database");
+        (pool, dir)
+    }
+
+    fn book(slug: &str, title: &str) -> BookLink {
+        BookLink {
+            slug: slug.to_string(),
+            title: title.to_string(),
+        }
+    }
+
+    fn stats(slug: &str, copies_sold: u64) -> BookStats {
+        BookStats {
+            slug: slug.to_string(),
+            copies_sold,
+            royalties_usd: 12.5,
+            readers: 3,
+            last_published_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn save_snapshot_inserts_book_and_snapshot_row() {
+        let (pool, _dir) = test_db().await;
+        let books = vec![book("my-book", "My Book")];
+        let stats = vec![stats("my-book", 10)];
+
+        save_snapshot(&pool, &books, &stats).await.expect("save_snapshot");
+
+        let title: String = sqlx::query_scalar("SELECT title FROM books WHERE slug = ?")
+            .bind("my-book")
+            .fetch_one(&pool)
+            .await
+            .expect("book row should exist");
+        assert_eq!(title, "My Book");
+
+        let copies: i64 = sqlx::query_scalar("SELECT copies_sold FROM book_snapshots WHERE slug = ?")
+            .bind("my-book")
+            .fetch_one(&pool)
+            .await
+            .expect("snapshot row should exist");
+        assert_eq!(copies, 10);
+    }
+
+    #[tokio::test]
+    async fn save_snapshot_upserts_title_on_repeat_runs() {
+        let (pool, _dir) = test_db().await;
+        let stats = vec![stats("my-book", 10)];
+
+        save_snapshot(&pool, &[book("my-book", "Old Title")], &stats)
+            .await
+            .expect("first save_snapshot");
+        save_snapshot(&pool, &[book("my-book", "New Title")], &stats)
+            .await
+            .expect("second save_snapshot");
+
+        let title: String = sqlx::query_scalar("SELECT title FROM books WHERE slug = ?")
+            .bind("my-book")
+            .fetch_one(&pool)
+            .await
+            .expect("book row should exist");
+        assert_eq!(title, "New Title");
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM books")
+            .fetch_one(&pool)
+            .await
+            .expect("count books");
+        assert_eq!(count, 1, "upsert must not duplicate the books row");
+    }
+
+    #[tokio::test]
+    async fn save_snapshot_skips_stats_with_no_matching_book() {
+        let (pool, _dir) = test_db().await;
+        let stats = vec![stats("unknown-book", 5)];
+
+        save_snapshot(&pool, &[], &stats).await.expect("save_snapshot");
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM book_snapshots")
+            .fetch_one(&pool)
+            .await
+            .expect("count snapshots");
+        assert_eq!(count, 0);
+    }
+}